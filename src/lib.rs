@@ -1,206 +1,509 @@
-/// The Michael Jackson Graph API
-///
-/// ## Examples
-/// ```rust
-/// use michael_jackson::Graph;
-/// let x = Graph::new();
-/// ```
+//! The Michael Jackson Graph API
+//!
+//! ## Examples
+//! ```rust
+//! use michael_jackson::Graph;
+//! let x: Graph<(), ()> = Graph::new();
+//! ```
 
 use std::collections::LinkedList;
 use std::collections::HashMap;
-use std::collections::linked_list;
+use std::collections::hash_map::Entry;
+use std::collections::BinaryHeap;
 use std::marker::PhantomData;
-use std::ops::Deref;
+use std::ops::Index;
+use std::ops::IndexMut;
+use std::ops::Add;
 use std::hash::Hash;
 use std::cmp::Eq;
+use std::cmp::Reverse;
+use std::fmt;
 
-/// A data structure which represents a mathematical graph.
-/// It is implemented as an adjacency list (a vector of Linked Lists) together
-/// with a Vector of vertices.
-pub struct Graph<V, E> {
-    adj_list: Vec<LinkedList<Edge<E>>>,
-    vertices: Vec<Vertex<V>>,
+/// Marker trait distinguishing directed from undirected graphs. It is
+/// implemented only by the zero-sized `Directed` and `Undirected` types, so
+/// the edge-type parameter `Ty` never occupies any space in a `Graph`.
+pub trait EdgeType {
+    /// Returns `true` when edges are one-directional.
+    fn is_directed() -> bool;
+}
+
+/// Marker type for a directed graph. Uninhabited, so it only ever appears as a
+/// type parameter.
+pub enum Directed {}
+/// Marker type for an undirected graph. Uninhabited, so it only ever appears as
+/// a type parameter.
+pub enum Undirected {}
+
+impl EdgeType for Directed {
+    fn is_directed() -> bool { true }
+}
+impl EdgeType for Undirected {
+    fn is_directed() -> bool { false }
+}
+
+/// The integer type used to store vertex and edge endpoint indices. Narrower
+/// widths (`u32`, `u16`) shrink the per-edge footprint of large graphs versus
+/// `usize`. The largest value is reserved as a null sentinel and must never be
+/// a real index, which is why the trait is `unsafe` to implement.
+///
+/// # Safety
+///
+/// `new` and `index` must round-trip every valid index losslessly, and
+/// `max_index` must return a value that the implementor guarantees will never
+/// be produced by `new` for a live index. The free list relies on `max_index`
+/// being a distinct end-of-list sentinel; an implementation that returns a
+/// reachable index from `max_index` corrupts the list.
+pub unsafe trait IndexType: Copy + Default + Ord + Hash {
+    /// Narrows a `usize` into this index type.
+    fn new(x: usize) -> Self;
+    /// Widens this index back into a `usize` for internal arithmetic.
+    fn index(&self) -> usize;
+    /// The reserved null sentinel (the type's maximum value).
+    fn max_index() -> Self;
+}
+
+unsafe impl IndexType for u32 {
+    fn new(x: usize) -> Self { x as u32 }
+    fn index(&self) -> usize { *self as usize }
+    fn max_index() -> Self { u32::MAX }
+}
+unsafe impl IndexType for u16 {
+    fn new(x: usize) -> Self { x as u16 }
+    fn index(&self) -> usize { *self as usize }
+    fn max_index() -> Self { u16::MAX }
+}
+unsafe impl IndexType for usize {
+    fn new(x: usize) -> Self { x }
+    fn index(&self) -> usize { *self }
+    fn max_index() -> Self { usize::MAX }
+}
+
+/// The additive identity for a distance measure. Defined in-crate since the
+/// standard library has no `Zero`; implemented for the integer types that can
+/// serve as Dijkstra edge costs.
+pub trait Zero {
+    fn zero() -> Self;
+}
+
+macro_rules! impl_zero {
+    ($($t:ty),*) => {
+        $(impl Zero for $t {
+            fn zero() -> Self { 0 }
+        })*
+    };
+}
+impl_zero!(u16, u32, u64, usize, i16, i32, i64, isize);
+
+/// The direction in which to walk the adjacency of a vertex. Only meaningful
+/// for directed graphs; for undirected graphs both values yield every
+/// neighbor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    /// Follow `Edge::child` targets of edges whose `parent` is the vertex.
+    Outgoing,
+    /// Follow `Edge::parent` sources of edges whose `child` is the vertex.
+    Incoming,
+}
+
+/// A lightweight handle to a vertex. It is a plain index into the graph's
+/// `vertices`/`adj_list`, carries no borrow, and is `Copy`, so callers can
+/// cache handles and keep mutating the graph through `&mut self`. Handles stay
+/// valid across unrelated removals. The `Ix` width defaults to `u32`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct NodeIndex<Ix = u32>(Ix);
+
+impl<Ix: IndexType> NodeIndex<Ix> {
+    fn new(x: usize) -> Self {
+        NodeIndex(Ix::new(x))
+    }
+    fn index(&self) -> usize {
+        self.0.index()
+    }
+}
+
+/// A slot in the `vertices` vector. Occupied slots hold vertex data; vacant
+/// slots carry the index of the next free slot (with `Ix::max_index()` marking the
+/// end), threading a singly-linked free list through the vector so indices can
+/// be reused without shifting the rest.
+enum Slot<V, Ix> {
+    Occupied(V),
+    Vacant(Ix),
 }
 
-/// A vertex, can be inserted into a Graph and holds data of arbitrary type.
-pub struct Vertex<V> {
-    contents: V,
+/// A data structure which represents a mathematical graph.
+/// It is implemented as an adjacency list (a vector of Linked Lists) together
+/// with a Vector of vertices. The `Ty` parameter selects directed or
+/// undirected edges and the `Ix` parameter selects the index width. Removed
+/// slots are recycled through a free list so handles remain stable across
+/// removals.
+pub struct Graph<V, E, Ty = Undirected, Ix = u32> {
+    adj_list: Vec<LinkedList<Edge<E, Ix>>>,
+    vertices: Vec<Slot<V, Ix>>,
+    free: Ix,
+    node_count: usize,
+    ty: PhantomData<Ty>,
 }
 
 /// A private struct in the Graph's adjacency list which keeps indices to
 /// both endpoints and the data associated with the edge.
-struct Edge<E> {
-    parent: usize,
-    child: usize,
+struct Edge<E, Ix> {
+    parent: Ix,
+    child: Ix,
     weight: E,
 }
 
-/// Iterator struct which keeps track of the location of a vertex within the Graph
-/// struct. Can be used to iterate over vertices in an arbitrary order.
-pub struct VRef<'a, V: 'a, E: 'a> {
-    index: usize,
-    graph: &'a Graph<V, E>,
-}
-impl<'a, V, E> Clone for VRef<'a, V, E> {
-    fn clone(&self) -> Self {
-        VRef{ index: self.index, graph: self.graph }
+/// Removes the first edge in `list` whose `child` matches, returning its
+/// weight, and leaves the remaining edges untouched.
+fn splice_edge<E, Ix: IndexType>(list: &mut LinkedList<Edge<E, Ix>>, child: usize) -> Option<E> {
+    let mut kept = LinkedList::new();
+    let mut found = None;
+    while let Some(edge) = list.pop_front() {
+        if found.is_none() && edge.child.index() == child {
+            found = Some(edge.weight);
+        } else {
+            kept.push_back(edge);
+        }
     }
+    *list = kept;
+    found
 }
-impl<'a, V, E> Copy for VRef<'a, V, E> {
+
+/// A disjoint-set (union-find) structure with union-by-rank and path
+/// compression, used to label connected components in near-linear time.
+struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<usize>,
 }
 
-impl<'a, V, E> Deref for VRef<'a, V, E> {
-    type Target = V;
-    fn deref(&self) -> &V {
-        unimplemented!()
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        UnionFind{ parent: (0..n).collect(), rank: vec![0; n] }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        if self.parent[x] != x {
+            let root = self.find(self.parent[x]);
+            self.parent[x] = root;
+        }
+        self.parent[x]
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let ra = self.find(a);
+        let rb = self.find(b);
+        if ra == rb {
+            return;
+        }
+        if self.rank[ra] < self.rank[rb] {
+            self.parent[ra] = rb;
+        } else if self.rank[ra] > self.rank[rb] {
+            self.parent[rb] = ra;
+        } else {
+            self.parent[rb] = ra;
+            self.rank[ra] += 1;
+        }
     }
 }
 
-pub struct VIter<'a, V: 'a, E: 'a> {
-    r: VRef<'a, V, E>,
+/// Iterator over the `NodeIndex` of every live vertex, in arbitrary order.
+/// Vacant slots are skipped.
+pub struct VIter<'a, V: 'a, Ix: 'a = u32> {
+    slots: std::slice::Iter<'a, Slot<V, Ix>>,
+    index: usize,
 }
 
-impl<'a, V, E> Iterator for VIter<'a, V, E> {
-    type Item = VRef<'a, V, E>;
+impl<'a, V, Ix: IndexType> Iterator for VIter<'a, V, Ix> {
+    type Item = NodeIndex<Ix>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.r.index < self.r.graph.num_vertices() {
-            let old_ref = VRef{ index: self.r.index, graph: self.r.graph };
-            self.r.index = self.r.index + 1;
-            Some(old_ref)
-        }
-        else {
-            None
+        for slot in self.slots.by_ref() {
+            let idx = self.index;
+            self.index += 1;
+            if let Slot::Occupied(_) = slot {
+                return Some(NodeIndex::new(idx));
+            }
         }
+        None
     }
 }
 
-pub struct eIter<'a, E: 'a> {
-    iter: linked_list::Iter<'a, Edge<E>>
+impl<V, E, Ty, Ix: IndexType> Index<NodeIndex<Ix>> for Graph<V, E, Ty, Ix> {
+    type Output = V;
+    fn index(&self, idx: NodeIndex<Ix>) -> &V {
+        match self.vertices[idx.index()] {
+            Slot::Occupied(ref v) => v,
+            Slot::Vacant(_) => panic!("no vertex at index {}", idx.index()),
+        }
+    }
 }
 
-impl<'a, E> Deref for eIter<'a, E> {
-    type Target = E;
-    fn deref(&self) -> &E {
-        unimplemented!()
+impl<V, E, Ty, Ix: IndexType> IndexMut<NodeIndex<Ix>> for Graph<V, E, Ty, Ix> {
+    fn index_mut(&mut self, idx: NodeIndex<Ix>) -> &mut V {
+        match self.vertices[idx.index()] {
+            Slot::Occupied(ref mut v) => v,
+            Slot::Vacant(_) => panic!("no vertex at index {}", idx.index()),
+        }
     }
 }
 
 // Some functions require types V and E to have default values
-impl<'a, V: Hash + Eq + Copy + Clone, E: Default> Graph<V, E> {
-    /// Returns an iterator that ranges over all vertices in arbitrary order.
-    pub fn vertices(&'a self) -> VIter<'a, V, E> {
-        VIter{ r: VRef{ index: 0, graph: &self } }
-    }
-
-    /// Construct a graph without data, with default values for V and E
-    /// and populates the given iters vector with iterators to the added vectors
-    /// in the order they were encountered. Clears the given vector before
-    /// populating.
-    #[allow(unused_variables)]
-    pub fn extend_with_edges(&'a self, edges: &Vec<(V, V)>) -> Vec<VRef<'a, V, E>> {
-        let mut vrefs : Vec<VRef<'a, V, E>> = Vec::new();
+impl<V: Hash + Eq + Copy + Clone, E: Default + Clone, Ty: EdgeType, Ix: IndexType> Graph<V, E, Ty, Ix> {
+    /// Extends the graph with the given edges, deduplicating endpoints by
+    /// value, and returns the handles of the vertices that were freshly
+    /// inserted, in the order they were first encountered.
+    pub fn extend_with_edges(&mut self, edges: &Vec<(V, V)>) -> Vec<NodeIndex<Ix>> {
+        let mut added : Vec<NodeIndex<Ix>> = Vec::new();
         let mut ref_map = HashMap::new();
 
-        for vref in self.vertices() {
-            ref_map.insert(*vref, vref);
+        for idx in 0..self.vertices.len() {
+            if let Slot::Occupied(ref data) = self.vertices[idx] {
+                ref_map.insert(*data, NodeIndex::new(idx));
+            }
         }
 
         for &(u, v) in edges {
-                if !ref_map.contains_key(&u) {
-                    let vref : VRef<'a, V, E> = self.add_vertex(u);
-                    vrefs.push(vref);
-                    ref_map.insert(u, vref);
+            let a = match ref_map.entry(u) {
+                Entry::Occupied(e) => *e.get(),
+                Entry::Vacant(e) => {
+                    let idx = self.add_vertex(u);
+                    added.push(idx);
+                    *e.insert(idx)
                 }
-                if !ref_map.contains_key(&v) {
-                    let vref = self.add_vertex(v);
-                    vrefs.push(vref);
-                    ref_map.insert(v, vref);
+            };
+            let b = match ref_map.entry(v) {
+                Entry::Occupied(e) => *e.get(),
+                Entry::Vacant(e) => {
+                    let idx = self.add_vertex(v);
+                    added.push(idx);
+                    *e.insert(idx)
                 }
-            self.add_edge(ref_map.get(&u).unwrap(), ref_map.get(&v).unwrap(), E::default());
-        };
-        vrefs
+            };
+            self.add_edge(a, b, E::default());
+        }
+        added
     }
+
     /// Construct a graph without data, with default values for V and E
-    #[allow(unused_variables)]
     pub fn new_from_edges(edges: &Vec<(V, V)>) -> Self {
-        //let mut v = Vec::new();
-        //let g: Graph<V, E> = Graph::new_from_edges_populate_iters(edges, &mut v);
-        //return g;
-        unimplemented!()
+        let mut g = Graph::new();
+        g.extend_with_edges(edges);
+        g
     }
 }
 
-impl<V, E> Graph<V, E> {
+impl<V, E, Ty: EdgeType, Ix: IndexType> Default for Graph<V, E, Ty, Ix> {
+    fn default() -> Self {
+        Graph::new()
+    }
+}
+
+impl<V, E, Ty: EdgeType, Ix: IndexType> Graph<V, E, Ty, Ix> {
     /// Create a new, empty graph
     pub fn new() -> Self {
-        Graph{ adj_list: Vec::new(), vertices: Vec::new() }
+        Graph{ adj_list: Vec::new(), vertices: Vec::new(), free: Ix::max_index(), node_count: 0, ty: PhantomData }
     }
 
-    /// Add a vertex to a graph, returning an VRef to the inserted vertex.
-    /// The lifetime of the VRef is limited to the lifetime of the inserted
-    /// vertex.
-    #[allow(unused_variables)]
-    //pub fn add_vertex(&'a mut self, v: Vertex<V>) -> 'a VRef<V, E> {
-    //TODO Alex, is it even possible to put a lifetime to a nonreference opject
-    //as we want to do here? We want to ensure that VRef will not outlive the
-    //graph for saftey reasons.
-    //One of our ideas for making this work would be to have an VRef contain a
-    //reference to an index and insist the the VRef not outlive that reference.
-    //We could then return an VRef out of references that do not outlive
-    //their graph.
-    pub fn add_vertex(&self, v: V) -> VRef<V, E> {
-        unimplemented!()
-        //VRef<V, E> { index: }
+    /// Returns an iterator over the handles of all live vertices, in arbitrary
+    /// order.
+    pub fn vertices(&self) -> VIter<'_, V, Ix> {
+        VIter{ slots: self.vertices.iter(), index: 0 }
+    }
+
+    /// Returns an iterator over the data of all live vertices, in index order.
+    pub fn node_weights(&self) -> impl Iterator<Item = &V> {
+        self.vertices.iter().filter_map(|slot| match slot {
+            Slot::Occupied(v) => Some(v),
+            Slot::Vacant(_) => None,
+        })
+    }
+
+    /// Add a vertex to the graph, reusing a free slot when one is available and
+    /// otherwise growing `vertices`/`adj_list`, and returns its handle.
+    pub fn add_vertex(&mut self, v: V) -> NodeIndex<Ix> {
+        self.node_count += 1;
+        if self.free != Ix::max_index() {
+            let idx = self.free.index();
+            let next = match self.vertices[idx] {
+                Slot::Vacant(next) => next,
+                Slot::Occupied(_) => unreachable!(),
+            };
+            self.free = next;
+            self.vertices[idx] = Slot::Occupied(v);
+            NodeIndex::new(idx)
+        } else {
+            let idx = self.vertices.len();
+            assert!(idx < Ix::max_index().index(), "graph is full for its index type");
+            self.vertices.push(Slot::Occupied(v));
+            self.adj_list.push(LinkedList::new());
+            NodeIndex::new(idx)
+        }
+    }
+
+    /// Removes a vertex and every edge incident to it, returning the vertex's
+    /// data. The freed slot is spliced onto the free list for reuse; all other
+    /// handles stay valid. Returns `None` if the slot was already vacant.
+    pub fn remove_vertex(&mut self, v: NodeIndex<Ix>) -> Option<V> {
+        if let Slot::Vacant(_) = self.vertices[v.index()] {
+            return None;
+        }
+        let vi = v.index();
+        for list in self.adj_list.iter_mut() {
+            let mut kept = LinkedList::new();
+            while let Some(edge) = list.pop_front() {
+                if edge.parent.index() != vi && edge.child.index() != vi {
+                    kept.push_back(edge);
+                }
+            }
+            *list = kept;
+        }
+        let old = std::mem::replace(&mut self.vertices[vi], Slot::Vacant(self.free));
+        self.free = Ix::new(vi);
+        self.node_count -= 1;
+        match old {
+            Slot::Occupied(data) => Some(data),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    /// Removes the edge between `v1` and `v2` (and its reverse entry for
+    /// undirected graphs), returning the stored weight if such an edge existed.
+    pub fn remove_edge(&mut self, v1: NodeIndex<Ix>, v2: NodeIndex<Ix>) -> Option<E> {
+        let removed = splice_edge(&mut self.adj_list[v1.index()], v2.index());
+        if removed.is_some() && !Ty::is_directed() {
+            splice_edge(&mut self.adj_list[v2.index()], v1.index());
+        }
+        removed
     }
 
     /// Add an edge to a graph if there is not currently an edge between those
     /// vertices.  Returns true if successful, and false otherwise.
-    #[allow(unused_variables)]
-    pub fn add_edge(&self, v1: &VRef<V, E>, v2: &VRef<V, E>, value: E) ->
-        Option<eIter<E>> {
-        //TODO Ask Alex if this return type is weird (gets back to the "should
-        //we have edge VRefs?" question).
-        unimplemented!()
+    /// The reverse adjacency entry is only registered for undirected graphs;
+    /// for directed graphs the edge is recorded from `v1` to `v2` alone.
+    pub fn add_edge(&mut self, v1: NodeIndex<Ix>, v2: NodeIndex<Ix>, value: E) -> bool
+        where E: Clone {
+        let (a, b) = (v1.index(), v2.index());
+        if self.adj_list[a].iter().any(|edge| edge.child.index() == b) {
+            return false;
+        }
+        self.adj_list[a].push_back(Edge{ parent: Ix::new(a), child: Ix::new(b), weight: value.clone() });
+        if !Ty::is_directed() {
+            self.adj_list[b].push_back(Edge{ parent: Ix::new(b), child: Ix::new(a), weight: value });
+        }
+        true
     }
 
     /// Returns the old value associated with vertex v and replaces it with the
     /// given value.
-    #[allow(unused_variables)]
-    pub fn replace_vertex(&self, v: &VRef<V, E>, value: V) -> PhantomData<V> {
-        Default::default()
+    pub fn replace_vertex(&mut self, v: NodeIndex<Ix>, value: V) -> V {
+        match self.vertices[v.index()] {
+            Slot::Occupied(ref mut data) => std::mem::replace(data, value),
+            Slot::Vacant(_) => panic!("no vertex at index {}", v.index()),
+        }
     }
 
     /// Returns the E which was stored between vertices v1 and v2, leaving the
     /// value in its place, unless there was no such edge, in which case it
     /// lets the value die and returns None.
-    #[allow(unused_variables)]
-    pub fn replace_edge(&self, v1: &VRef<V, E>, v2: &VRef<V, E>, value: E) ->
-        Option<E> {
-        None
+    pub fn replace_edge(&mut self, v1: NodeIndex<Ix>, v2: NodeIndex<Ix>, value: E) -> Option<E>
+        where E: Clone {
+        let (a, b) = (v1.index(), v2.index());
+        let mut old = None;
+        for edge in self.adj_list[a].iter_mut() {
+            if edge.child.index() == b {
+                old = Some(std::mem::replace(&mut edge.weight, value.clone()));
+                break;
+            }
+        }
+        if old.is_some() && !Ty::is_directed() {
+            for edge in self.adj_list[b].iter_mut() {
+                if edge.child.index() == a {
+                    edge.weight = value;
+                    break;
+                }
+            }
+        }
+        old
     }
 
-    /// Returns a vector of terators neighboring the given vertex.
-    #[allow(unused_variables)]
-    pub fn get_neighbors(&self, v: &VRef<V, E>) -> Vec<VRef<V, E>> {
-        Vec::new()
+    /// Returns a vector of neighbors of the given vertex. For directed graphs
+    /// `direction` selects whether to follow outgoing (`Edge::child`) or
+    /// incoming (`Edge::parent`) edges; undirected graphs ignore it and return
+    /// every neighbor.
+    pub fn get_neighbors(&self, v: NodeIndex<Ix>, direction: Direction) -> Vec<NodeIndex<Ix>> {
+        let mut neighbors = Vec::new();
+        if !Ty::is_directed() || direction == Direction::Outgoing {
+            for edge in &self.adj_list[v.index()] {
+                neighbors.push(NodeIndex::new(edge.child.index()));
+            }
+        } else {
+            let vi = v.index();
+            for list in &self.adj_list {
+                for edge in list {
+                    if edge.child.index() == vi {
+                        neighbors.push(NodeIndex::new(edge.parent.index()));
+                    }
+                }
+            }
+        }
+        neighbors
     }
 
-    /// Returns whether or not the given vertices are adjacent.
-    #[allow(unused_variables)]
-    pub fn adjacent(&self, v1: &VRef<V, E>, v2: &VRef<V, E>) -> bool {
-        true
+    /// Returns whether or not the given vertices are adjacent. For directed
+    /// graphs this tests for an edge from `v1` to `v2`.
+    pub fn adjacent(&self, v1: NodeIndex<Ix>, v2: NodeIndex<Ix>) -> bool {
+        let b = v2.index();
+        self.adj_list[v1.index()].iter().any(|edge| edge.child.index() == b)
     }
 
-    /// Returns the number of vertices in the graph.
-    #[allow(unused_variables)]
+    /// Returns the number of live vertices in the graph.
     pub fn num_vertices(&self) -> usize {
-        0
+        self.node_count
     }
 
-    /// Returns the number of edges in the graph.
-    #[allow(unused_variables)]
+    /// Returns the number of edges in the graph. Undirected graphs store each
+    /// edge from both endpoints, so the raw adjacency count is halved.
     pub fn num_edges(&self) -> usize {
-        0
+        let total: usize = self.adj_list.iter().map(|list| list.len()).sum();
+        if Ty::is_directed() { total } else { total / 2 }
+    }
+
+    /// Computes single-source shortest paths from `start` using Dijkstra's
+    /// algorithm. Edge weights are mapped to a comparable distance by `measure`
+    /// (so `E` itself need not be the distance), costs are accumulated with a
+    /// binary min-heap, and the map of finalized minimal costs is returned. When
+    /// `goal` is `Some`, the search stops as soon as that vertex is finalized.
+    pub fn dijkstra<K, F>(&self, start: NodeIndex<Ix>, goal: Option<NodeIndex<Ix>>, measure: F)
+        -> HashMap<NodeIndex<Ix>, K>
+        where K: Copy + Ord + Add<Output = K> + Zero, F: Fn(&E) -> K {
+        let mut dist: HashMap<NodeIndex<Ix>, K> = HashMap::new();
+        let mut finalized: HashMap<NodeIndex<Ix>, K> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        dist.insert(start, K::zero());
+        heap.push((Reverse(K::zero()), start));
+        while let Some((Reverse(cost), node)) = heap.pop() {
+            if finalized.contains_key(&node) {
+                continue;
+            }
+            finalized.insert(node, cost);
+            if goal == Some(node) {
+                break;
+            }
+            for edge in &self.adj_list[node.index()] {
+                let next = NodeIndex::new(edge.child.index());
+                if finalized.contains_key(&next) {
+                    continue;
+                }
+                let ncost = cost + measure(&edge.weight);
+                let improved = match dist.get(&next) {
+                    Some(&d) => ncost < d,
+                    None => true,
+                };
+                if improved {
+                    dist.insert(next, ncost);
+                    heap.push((Reverse(ncost), next));
+                }
+            }
+        }
+        finalized
     }
 
     /// Returns the adjacency matrix for the given graph.
@@ -216,20 +519,505 @@ impl<V, E> Graph<V, E> {
         Vec::new()
     }
 
-    /// Returns the number of connected components in the graph.
-    #[allow(unused_variables)]
+    /// Returns the number of connected components in the graph. For directed
+    /// graphs this counts *weakly* connected components (edges are treated as
+    /// undirected during the union).
     pub fn num_components(&self) -> usize {
-        1
+        let mut uf = UnionFind::new(self.vertices.len());
+        for list in &self.adj_list {
+            for edge in list {
+                uf.union(edge.parent.index(), edge.child.index());
+            }
+        }
+        let mut count = 0;
+        for i in 0..self.vertices.len() {
+            if let Slot::Occupied(_) = self.vertices[i] {
+                if uf.find(i) == i {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Returns a canonical component id in `0..num_components()` for every slot,
+    /// indexed by `NodeIndex`. Vacant slots (from removals) are left as
+    /// `usize::max_value()`. As with `num_components`, directed graphs yield
+    /// weakly connected components.
+    pub fn connected_components(&self) -> Vec<usize> {
+        let mut uf = UnionFind::new(self.vertices.len());
+        for list in &self.adj_list {
+            for edge in list {
+                uf.union(edge.parent.index(), edge.child.index());
+            }
+        }
+        let mut labels = vec![usize::MAX; self.vertices.len()];
+        let mut remap = HashMap::new();
+        let mut next = 0;
+        for (i, slot) in self.vertices.iter().enumerate() {
+            if let Slot::Occupied(_) = slot {
+                let root = uf.find(i);
+                let id = match remap.get(&root) {
+                    Some(&id) => id,
+                    None => {
+                        let c = next;
+                        remap.insert(root, c);
+                        next += 1;
+                        c
+                    }
+                };
+                labels[i] = id;
+            }
+        }
+        labels
+    }
+
+    /// Returns a `Dot` wrapper rendering the graph in Graphviz format with the
+    /// default configuration. `println!("{}", graph.dot())` produces a block
+    /// that can be piped straight into `dot -Tpng`.
+    pub fn dot(&self) -> Dot<'_, V, E, Ty, Ix> {
+        Dot{ graph: self, config: DotConfig::default() }
+    }
+
+    /// Like `dot`, but with an explicit `DotConfig` controlling label escaping
+    /// and weight display.
+    pub fn dot_with_config(&self, config: DotConfig) -> Dot<'_, V, E, Ty, Ix> {
+        Dot{ graph: self, config }
+    }
+
+    /// Renders the graph to a Graphviz `digraph`/`graph` string.
+    pub fn to_dot(&self) -> String
+        where V: fmt::Display, E: fmt::Display {
+        self.dot().to_string()
+    }
+}
+
+/// Controls how a `Dot` renders labels.
+pub struct DotConfig {
+    /// Escape `"`, `\` and newlines inside label strings.
+    pub escape_labels: bool,
+    /// Emit `[label="…"]` from the edge weight; suppress it when false.
+    pub show_weights: bool,
+}
+
+impl Default for DotConfig {
+    fn default() -> Self {
+        DotConfig{ escape_labels: true, show_weights: true }
+    }
+}
+
+/// A `Display` adapter that renders a `Graph` in the Graphviz DOT language.
+/// Edges use `->` for directed graphs and `--` for undirected ones.
+pub struct Dot<'a, V: 'a, E: 'a, Ty: 'a, Ix: 'a> {
+    graph: &'a Graph<V, E, Ty, Ix>,
+    config: DotConfig,
+}
+
+/// Escapes the characters that would otherwise terminate or corrupt a quoted
+/// DOT label.
+fn escape_label(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+impl<'a, V, E, Ty, Ix> Dot<'a, V, E, Ty, Ix> {
+    fn label<T: fmt::Display>(&self, value: &T) -> String {
+        let s = format!("{}", value);
+        if self.config.escape_labels { escape_label(&s) } else { s }
+    }
+}
+
+impl<'a, V, E, Ty, Ix> fmt::Display for Dot<'a, V, E, Ty, Ix>
+    where V: fmt::Display, E: fmt::Display, Ty: EdgeType, Ix: IndexType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let g = self.graph;
+        let directed = Ty::is_directed();
+        let (kind, edgeop) = if directed { ("digraph", "->") } else { ("graph", "--") };
+        writeln!(f, "{} {{", kind)?;
+        for node in g.vertices() {
+            writeln!(f, "    {} [label=\"{}\"];", node.index(), self.label(&g[node]))?;
+        }
+        for list in &g.adj_list {
+            let mut loop_seen = false;
+            for edge in list {
+                let (p, c) = (edge.parent.index(), edge.child.index());
+                // Undirected edges are stored from both endpoints (a self-loop
+                // twice in the same list); emit each once.
+                if !directed && (p > c || (p == c && loop_seen)) {
+                    continue;
+                }
+                if p == c {
+                    loop_seen = true;
+                }
+                if self.config.show_weights {
+                    writeln!(f, "    {} {} {} [label=\"{}\"];", p, edgeop, c, self.label(&edge.weight))?;
+                } else {
+                    writeln!(f, "    {} {} {};", p, edgeop, c)?;
+                }
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
+/// A graph whose vertices are addressable directly by their label. `V` is both
+/// the vertex data and the key, so there is no separate handle map to thread
+/// through callers: `add_edge`, `adjacent`, `get_neighbors` and `replace_edge`
+/// all take labels. Duplicate labels are deduplicated — inserting an existing
+/// label returns its handle without creating a second vertex.
+pub struct GraphMap<V, E, Ty = Undirected, Ix = u32>
+    where V: Hash + Eq + Copy {
+    graph: Graph<V, E, Ty, Ix>,
+    nodes: HashMap<V, usize>,
+}
+
+impl<V, E, Ty, Ix> Default for GraphMap<V, E, Ty, Ix>
+    where V: Hash + Eq + Copy, Ty: EdgeType, Ix: IndexType {
+    fn default() -> Self {
+        GraphMap::new()
+    }
+}
+
+impl<V, E, Ty, Ix> GraphMap<V, E, Ty, Ix>
+    where V: Hash + Eq + Copy, Ty: EdgeType, Ix: IndexType {
+    /// Create a new, empty label-indexed graph.
+    pub fn new() -> Self {
+        GraphMap{ graph: Graph::new(), nodes: HashMap::new() }
+    }
+
+    /// Inserts a vertex for `label` if it is absent and returns its handle. If
+    /// the label already exists the existing handle is returned and no second
+    /// vertex is created.
+    pub fn add_node(&mut self, label: V) -> NodeIndex<Ix> {
+        if let Some(&slot) = self.nodes.get(&label) {
+            return NodeIndex::new(slot);
+        }
+        let idx = self.graph.add_vertex(label);
+        self.nodes.insert(label, idx.index());
+        idx
+    }
+
+    /// Returns whether a vertex with the given label is present.
+    pub fn contains_node(&self, label: V) -> bool {
+        self.nodes.contains_key(&label)
+    }
+
+    /// Adds an edge between the two labels, inserting either endpoint on demand,
+    /// and returns whether the edge was newly created.
+    pub fn add_edge(&mut self, a: V, b: V, weight: E) -> bool
+        where E: Clone {
+        let ai = self.add_node(a);
+        let bi = self.add_node(b);
+        self.graph.add_edge(ai, bi, weight)
+    }
+
+    /// Returns whether the two labels are adjacent, or `false` if either is
+    /// absent.
+    pub fn adjacent(&self, a: V, b: V) -> bool {
+        match (self.nodes.get(&a), self.nodes.get(&b)) {
+            (Some(&ai), Some(&bi)) => self.graph.adjacent(NodeIndex::new(ai), NodeIndex::new(bi)),
+            _ => false,
+        }
+    }
+
+    /// Returns the labels of the neighbors of `label`, or an empty vector if the
+    /// label is absent. `direction` is honored exactly as in `Graph`.
+    pub fn get_neighbors(&self, label: V, direction: Direction) -> Vec<V> {
+        match self.nodes.get(&label) {
+            Some(&slot) => self.graph
+                .get_neighbors(NodeIndex::new(slot), direction)
+                .into_iter()
+                .map(|idx| self.graph[idx])
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Replaces the weight on the edge between the two labels, returning the old
+    /// weight, or `None` if either label or the edge is absent.
+    pub fn replace_edge(&mut self, a: V, b: V, weight: E) -> Option<E>
+        where E: Clone {
+        match (self.nodes.get(&a).cloned(), self.nodes.get(&b).cloned()) {
+            (Some(ai), Some(bi)) => self.graph.replace_edge(NodeIndex::new(ai), NodeIndex::new(bi), weight),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of live vertices.
+    pub fn num_nodes(&self) -> usize {
+        self.graph.num_vertices()
+    }
+
+    /// Returns the number of edges.
+    pub fn num_edges(&self) -> usize {
+        self.graph.num_edges()
+    }
+}
+
+impl<V, E, Ty, Ix> GraphMap<V, E, Ty, Ix>
+    where V: Hash + Eq + Copy, E: Default + Clone, Ty: EdgeType, Ix: IndexType {
+    /// Builds a graph from an edge list, using default edge weights and
+    /// deduplicating endpoints by label.
+    pub fn new_from_edges(edges: &Vec<(V, V)>) -> Self {
+        let mut g = GraphMap::new();
+        for &(u, v) in edges {
+            g.add_edge(u, v, E::default());
+        }
+        g
+    }
+}
+
+/// `serde` support, gated behind the `serde` feature. A graph round-trips
+/// through a stable, compact representation — a `nodes` array of vertex weights
+/// and an `edges` array of `(parent, child, weight)` triples — instead of
+/// leaking the internal adjacency layout. Endpoint indices are validated on
+/// deserialize and the adjacency list is rebuilt from the edge list.
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::*;
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+    use serde::de::Error as DeError;
+
+    #[derive(Serialize, Deserialize)]
+    struct GraphData<V, E> {
+        nodes: Vec<V>,
+        edges: Vec<(usize, usize, E)>,
+    }
+
+    impl<V, E, Ty, Ix> Serialize for Graph<V, E, Ty, Ix>
+        where V: Serialize + Clone, E: Serialize + Clone, Ty: EdgeType, Ix: IndexType {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            // Compact the (possibly sparse) slots into a dense node array and
+            // remember each slot's dense position so edges refer to it.
+            let mut remap = vec![usize::MAX; self.vertices.len()];
+            let mut nodes = Vec::with_capacity(self.node_count);
+            for (i, slot) in self.vertices.iter().enumerate() {
+                if let Slot::Occupied(ref v) = slot {
+                    remap[i] = nodes.len();
+                    nodes.push(v.clone());
+                }
+            }
+            let directed = Ty::is_directed();
+            let mut edges = Vec::new();
+            for list in &self.adj_list {
+                let mut loop_seen = false;
+                for edge in list {
+                    let (p, c) = (edge.parent.index(), edge.child.index());
+                    // Undirected edges are stored twice; keep a single triple.
+                    if !directed && (p > c || (p == c && loop_seen)) {
+                        continue;
+                    }
+                    if p == c {
+                        loop_seen = true;
+                    }
+                    edges.push((remap[p], remap[c], edge.weight.clone()));
+                }
+            }
+            GraphData{ nodes, edges }.serialize(serializer)
+        }
+    }
+
+    impl<'de, V, E, Ty, Ix> Deserialize<'de> for Graph<V, E, Ty, Ix>
+        where V: Deserialize<'de>, E: Deserialize<'de> + Clone, Ty: EdgeType, Ix: IndexType {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let data = GraphData::<V, E>::deserialize(deserializer)?;
+            let n = data.nodes.len();
+            let mut g: Graph<V, E, Ty, Ix> = Graph::new();
+            for v in data.nodes {
+                g.add_vertex(v);
+            }
+            for (p, c, w) in data.edges {
+                if p >= n || c >= n {
+                    let bad = if p >= n { p } else { c };
+                    return Err(DeError::custom(format!("edge endpoint {} out of range for {} nodes", bad, n)));
+                }
+                g.add_edge(NodeIndex::new(p), NodeIndex::new(c), w);
+            }
+            Ok(g)
+        }
     }
 }
 
-/*
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
-    fn it_works() {
+    fn directed_neighbors_respect_direction() {
+        let mut g: Graph<i32, (), Directed> = Graph::new();
+        let a = g.add_vertex(1);
+        let b = g.add_vertex(2);
+        g.add_edge(a, b, ());
+        assert_eq!(g.get_neighbors(a, Direction::Outgoing), vec![b]);
+        assert!(g.get_neighbors(a, Direction::Incoming).is_empty());
+        assert_eq!(g.get_neighbors(b, Direction::Incoming), vec![a]);
+        assert!(g.get_neighbors(b, Direction::Outgoing).is_empty());
+        assert!(g.adjacent(a, b));
+        assert!(!g.adjacent(b, a));
+    }
+
+    #[test]
+    fn undirected_neighbors_are_symmetric() {
+        let mut g: Graph<i32, ()> = Graph::new();
+        let a = g.add_vertex(1);
+        let b = g.add_vertex(2);
+        g.add_edge(a, b, ());
+        assert_eq!(g.get_neighbors(a, Direction::Incoming), vec![b]);
+        assert_eq!(g.get_neighbors(b, Direction::Outgoing), vec![a]);
+        assert!(g.adjacent(a, b) && g.adjacent(b, a));
+    }
+
+    #[test]
+    fn node_index_is_a_copy_handle() {
+        let mut g: Graph<&str, ()> = Graph::new();
+        let a = g.add_vertex("a");
+        let b = g.add_vertex("b");
+        // The handle is `Copy`, survives further `&mut self` mutation, and
+        // indexes both immutably and mutably.
+        let a_again = a;
+        let _c = g.add_vertex("c");
+        assert_eq!(g[a], "a");
+        assert_eq!(g[a_again], "a");
+        assert_eq!(g[b], "b");
+        g[a] = "z";
+        assert_eq!(g[a], "z");
     }
-}
 
+    #[test]
+    fn removed_slot_is_reused_and_handles_stay_valid() {
+        let mut g: Graph<i32, ()> = Graph::new();
+        let a = g.add_vertex(10);
+        let b = g.add_vertex(20);
+        let c = g.add_vertex(30);
+        assert_eq!(g.num_vertices(), 3);
+        assert_eq!(g.remove_vertex(b), Some(20));
+        assert_eq!(g.remove_vertex(b), None); // already vacant
+        assert_eq!(g.num_vertices(), 2);
+        // Unrelated handles keep pointing at their data.
+        assert_eq!(g[a], 10);
+        assert_eq!(g[c], 30);
+        // The next insertion recycles the freed slot.
+        let d = g.add_vertex(40);
+        assert_eq!(d.index(), b.index());
+        assert_eq!(g[d], 40);
+    }
+
+    #[test]
+    fn remove_edge_returns_weight_and_clears_both_directions() {
+        let mut g: Graph<i32, i32> = Graph::new();
+        let a = g.add_vertex(1);
+        let b = g.add_vertex(2);
+        g.add_edge(a, b, 7);
+        assert_eq!(g.remove_edge(a, b), Some(7));
+        assert!(!g.adjacent(a, b) && !g.adjacent(b, a));
+        assert_eq!(g.remove_edge(a, b), None);
+    }
+
+    #[test]
+    fn index_width_can_be_narrowed() {
+        let mut g: Graph<i32, (), Undirected, u16> = Graph::new();
+        let a = g.add_vertex(1);
+        let b = g.add_vertex(2);
+        g.add_edge(a, b, ());
+        assert!(g.adjacent(a, b));
+        assert_eq!(g.num_vertices(), 2);
+        assert_eq!(std::mem::size_of::<NodeIndex<u16>>(), 2);
+    }
+
+    #[test]
+    fn dot_renders_directed_and_undirected() {
+        let mut d: Graph<&str, i32, Directed> = Graph::new();
+        let a = d.add_vertex("a");
+        let b = d.add_vertex("b");
+        d.add_edge(a, b, 5);
+        let s = d.to_dot();
+        assert!(s.starts_with("digraph {"));
+        assert!(s.contains("0 [label=\"a\"];"));
+        assert!(s.contains("0 -> 1 [label=\"5\"];"));
 
-*/
+        let mut u: Graph<&str, i32> = Graph::new();
+        let x = u.add_vertex("x");
+        let y = u.add_vertex("y");
+        u.add_edge(x, y, 1);
+        let s = u.to_dot();
+        assert!(s.starts_with("graph {"));
+        assert!(s.contains("0 -- 1"));
+        // An undirected edge stored from both endpoints is still emitted once.
+        assert_eq!(s.matches(" -- ").count(), 1);
+    }
+
+    #[test]
+    fn graphmap_dedups_nodes_by_label() {
+        let mut g: GraphMap<&str, ()> = GraphMap::new();
+        g.add_edge("a", "b", ());
+        g.add_edge("a", "c", ()); // "a" is reused, not duplicated
+        assert_eq!(g.num_nodes(), 3);
+        assert!(g.contains_node("a"));
+        assert!(g.adjacent("a", "b"));
+        let mut ns = g.get_neighbors("a", Direction::Outgoing);
+        ns.sort();
+        assert_eq!(ns, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn connected_components_label_disjoint_groups() {
+        let mut g: Graph<i32, ()> = Graph::new();
+        let a = g.add_vertex(0);
+        let b = g.add_vertex(1);
+        let c = g.add_vertex(2);
+        let d = g.add_vertex(3);
+        g.add_edge(a, b, ());
+        g.add_edge(c, d, ());
+        assert_eq!(g.num_components(), 2);
+        let labels = g.connected_components();
+        assert_eq!(labels[a.index()], labels[b.index()]);
+        assert_eq!(labels[c.index()], labels[d.index()]);
+        assert_ne!(labels[a.index()], labels[c.index()]);
+    }
+
+    #[test]
+    fn dijkstra_finds_shortest_costs() {
+        let mut g: Graph<(), u32, Directed> = Graph::new();
+        let a = g.add_vertex(());
+        let b = g.add_vertex(());
+        let c = g.add_vertex(());
+        g.add_edge(a, b, 1);
+        g.add_edge(b, c, 2);
+        g.add_edge(a, c, 10);
+        let dist = g.dijkstra(a, None, |&w| w);
+        assert_eq!(dist[&a], 0);
+        assert_eq!(dist[&b], 1);
+        // a -> b -> c (3) beats the direct a -> c edge (10).
+        assert_eq!(dist[&c], 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn graph_round_trips_through_json() {
+        let mut g: Graph<i32, i32, Directed> = Graph::new();
+        let a = g.add_vertex(1);
+        let b = g.add_vertex(2);
+        g.add_edge(a, b, 9);
+        let json = serde_json::to_string(&g).unwrap();
+        let back: Graph<i32, i32, Directed> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.num_vertices(), 2);
+        assert_eq!(back.num_edges(), 1);
+        assert!(back.adjacent(NodeIndex::new(0), NodeIndex::new(1)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_rejects_out_of_range_endpoint() {
+        let json = r#"{"nodes":[1,2],"edges":[[0,5,9]]}"#;
+        let parsed: Result<Graph<i32, i32, Directed>, _> = serde_json::from_str(json);
+        assert!(parsed.is_err());
+    }
+}